@@ -0,0 +1,116 @@
+//! Single-flight coordination for concurrent cache misses on the same key.
+//! Modeled on Pingora's `CacheLock`: the first request for a given key becomes the "leader"
+//! and performs the upstream fetch, while concurrent requests for the same key ("followers")
+//! wait for the leader to finish writing the response to disk before re-reading the now-populated
+//! cache entry, instead of all independently racing upstream.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tokio::sync::futures::OwnedNotified;
+use tokio::sync::Notify;
+
+/// What a caller should do after requesting the lock for a cache key.
+pub enum CacheLockGuard {
+    /// The caller is the first in-flight request for this key. It must perform the miss itself,
+    /// then call [`CacheLocks::release`] with the same key once the response (successful or
+    /// not) has finished being written, so that followers can wake up.
+    Leader,
+    /// Another request for this key is already in flight. The caller should `await` this
+    /// future, then re-check the cache.
+    ///
+    /// This is already-constructed `Notify::notified_owned()` state rather than an `Arc<Notify>`
+    /// the caller would call `.notified()` on itself: `Notify::notify_waiters` only wakes
+    /// `Notified` futures that exist at the time it's called, so if we handed back the bare
+    /// `Arc<Notify>` instead, a leader that finishes and releases between `acquire` returning
+    /// and the caller's own `.notified()` call would leave the follower waiting for nothing and
+    /// blocking for the full lock timeout. Constructing it here, still under `acquire`'s DashMap
+    /// lookup, closes that window.
+    Follower(OwnedNotified),
+}
+
+/// Tracks in-flight cache misses, keyed by the cache hash used for the on-disk filename.
+pub struct CacheLocks {
+    in_flight: DashMap<String, Arc<Notify>>,
+}
+
+impl CacheLocks {
+    pub fn new() -> Self {
+        Self {
+            in_flight: DashMap::new(),
+        }
+    }
+
+    /// Attempts to become the leader for `key`. Returns [`CacheLockGuard::Leader`] if no other
+    /// request is currently populating this key, or [`CacheLockGuard::Follower`] with an
+    /// already-registered `Notify` future to await otherwise.
+    pub fn acquire(&self, key: &str) -> CacheLockGuard {
+        match self.in_flight.entry(key.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(entry) => {
+                CacheLockGuard::Follower(entry.get().clone().notified_owned())
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(Arc::new(Notify::new()));
+                CacheLockGuard::Leader
+            }
+        }
+    }
+
+    /// Wakes any followers waiting on `key` and removes the lock entry. Must be called by the
+    /// leader exactly once, on both the success and error paths, once the response has finished
+    /// being written (or it has been determined that nothing will be written).
+    pub fn release(&self, key: &str) {
+        if let Some((_, notify)) = self.in_flight.remove(key) {
+            notify.notify_waiters();
+        }
+    }
+}
+
+impl Default for CacheLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_acquire_second_caller_for_same_key_becomes_follower() {
+        let locks = CacheLocks::new();
+        assert!(matches!(locks.acquire("key"), CacheLockGuard::Leader));
+        assert!(matches!(locks.acquire("key"), CacheLockGuard::Follower(_)));
+    }
+
+    #[test]
+    fn test_acquire_different_keys_are_independent_leaders() {
+        let locks = CacheLocks::new();
+        assert!(matches!(locks.acquire("a"), CacheLockGuard::Leader));
+        assert!(matches!(locks.acquire("b"), CacheLockGuard::Leader));
+    }
+
+    #[test]
+    fn test_release_allows_a_new_leader_for_the_key() {
+        let locks = CacheLocks::new();
+        assert!(matches!(locks.acquire("key"), CacheLockGuard::Leader));
+        locks.release("key");
+        assert!(matches!(locks.acquire("key"), CacheLockGuard::Leader));
+    }
+
+    #[tokio::test]
+    async fn test_release_wakes_waiting_followers() {
+        let locks = CacheLocks::new();
+        assert!(matches!(locks.acquire("key"), CacheLockGuard::Leader));
+        let follower = match locks.acquire("key") {
+            CacheLockGuard::Follower(notified) => notified,
+            CacheLockGuard::Leader => panic!("expected a follower for an already-held key"),
+        };
+        locks.release("key");
+        tokio::time::timeout(Duration::from_secs(1), follower)
+            .await
+            .expect("follower should have been woken by release()");
+    }
+}
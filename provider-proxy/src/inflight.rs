@@ -0,0 +1,103 @@
+//! Tracks in-flight cache-write tasks so that graceful shutdown can wait for the last one to
+//! finish before returning, following the signalling-refcount drain approach Deno uses to avoid
+//! losing the final in-flight task during shutdown.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+#[derive(Default)]
+struct Inner {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+#[derive(Clone, Default)]
+pub struct InFlightWrites {
+    inner: Arc<Inner>,
+}
+
+impl InFlightWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks one cache-write task as started. The returned guard must be held for the lifetime
+    /// of that task and dropped once it finishes (successfully or not).
+    pub fn start(&self) -> InFlightGuard {
+        self.inner.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Waits until every task started via [`Self::start`] has had its guard dropped.
+    pub async fn drain(&self) {
+        loop {
+            // Subscribe before checking the count, so a `notify_waiters` racing with this check
+            // can't be missed between the two.
+            let idle = self.inner.idle.notified();
+            if self.inner.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+pub struct InFlightGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.inner.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_returns_immediately_with_nothing_in_flight() {
+        let writes = InFlightWrites::new();
+        tokio::time::timeout(Duration::from_millis(100), writes.drain())
+            .await
+            .expect("drain() should not block when nothing is in flight");
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_until_the_last_guard_is_dropped() {
+        let writes = InFlightWrites::new();
+        let first = writes.start();
+        let second = writes.start();
+
+        let waiter = writes.clone();
+        let drained = tokio::spawn(async move {
+            waiter.drain().await;
+        });
+
+        // Give the drain task a chance to run and subscribe to notifications.
+        tokio::task::yield_now().await;
+        assert!(!drained.is_finished());
+
+        drop(first);
+        tokio::task::yield_now().await;
+        assert!(
+            !drained.is_finished(),
+            "drain() must not resolve while a guard is still outstanding"
+        );
+
+        drop(second);
+        tokio::time::timeout(Duration::from_secs(1), drained)
+            .await
+            .expect("drain() should resolve once the last guard is dropped")
+            .unwrap();
+    }
+}
@@ -3,22 +3,29 @@
 //! with the openssl dependency and `default_client` removed.
 #![expect(clippy::panic, clippy::unwrap_used, clippy::expect_used)]
 
+mod cache_lock;
+mod inflight;
+mod lru_index;
 mod mitm_server;
 mod streaming_body_collector;
 mod tls;
 
 use std::future::Future;
-use std::io::Write;
+use std::io::{Read as _, Write};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context as _;
 use bytes::{Bytes, BytesMut};
+use cache_lock::{CacheLockGuard, CacheLocks};
 use clap::{Parser, ValueEnum};
 use http::{HeaderName, HeaderValue, Version};
 use http_body_util::{combinators::BoxBody, BodyExt, Full};
 use hyper::service::service_fn;
+use inflight::InFlightWrites;
+use lru_index::LruIndex;
 use mitm_server::MitmProxy;
 use moka::sync::Cache;
 use serde::Serialize;
@@ -29,6 +36,104 @@ use tracing::level_filters::LevelFilter;
 
 const CACHE_HEADER_NAME: &str = "x-tensorzero-provider-proxy-cache";
 
+/// Magic bytes identifying a zstd-compressed cache file, so that legacy uncompressed (plain
+/// JSON) cache entries can still be read without a version marker of our own.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// Magic bytes identifying a gzip-compressed cache file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decompresses a cache file's contents if it was written with [`CacheCompression::Zstd`] or
+/// [`CacheCompression::Gzip`] (detected via magic bytes), or returns it unchanged if it's a
+/// legacy (or [`CacheCompression::None`]) plain-JSON cache entry.
+fn decompress_cache_body(raw: Vec<u8>) -> Result<Vec<u8>, anyhow::Error> {
+    if raw.starts_with(&ZSTD_MAGIC) {
+        zstd::decode_all(raw.as_slice()).with_context(|| "Failed to zstd-decompress cache entry")
+    } else if raw.starts_with(&GZIP_MAGIC) {
+        let mut decoder = flate2::read::GzDecoder::new(raw.as_slice());
+        let mut out = Vec::new();
+        decoder
+            .read_to_end(&mut out)
+            .with_context(|| "Failed to gzip-decompress cache entry")?;
+        Ok(out)
+    } else {
+        Ok(raw)
+    }
+}
+
+/// Whether (and until when) a response may be cached, per its `Cache-Control`/`Expires`
+/// headers. Inspired by Pingora's `resp_cacheable` + `CacheMeta`.
+struct Freshness {
+    /// `false` if the response specified `no-store` or `private`.
+    cacheable: bool,
+    /// When this entry should be treated as stale and re-fetched, or `None` to cache
+    /// indefinitely (no TTL was specified).
+    expires_at: Option<SystemTime>,
+}
+
+fn resp_freshness(headers: &http::HeaderMap, now: SystemTime) -> Freshness {
+    let mut cacheable = true;
+    let mut max_age = None;
+    let mut s_maxage = None;
+    if let Some(cache_control) = headers
+        .get(http::header::CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+    {
+        for directive in cache_control.split(',') {
+            let directive = directive.trim().to_ascii_lowercase();
+            if directive == "no-store" || directive == "private" {
+                cacheable = false;
+            } else if let Some(value) = directive.strip_prefix("s-maxage=") {
+                s_maxage = value.trim().parse::<u64>().ok();
+            } else if let Some(value) = directive.strip_prefix("max-age=") {
+                max_age = value.trim().parse::<u64>().ok();
+            }
+        }
+    }
+    // `s-maxage` takes priority over `max-age` when both are present, per RFC 9111 §5.2.2.10.
+    let expires_at = s_maxage
+        .or(max_age)
+        .and_then(|secs| now.checked_add(Duration::from_secs(secs)))
+        .or_else(|| {
+            headers
+                .get(http::header::EXPIRES)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| httpdate::parse_http_date(v).ok())
+        });
+    Freshness {
+        cacheable,
+        expires_at,
+    }
+}
+
+/// A deserialized cache entry as held in [`ProxyState::memory_cache`].
+#[derive(Clone)]
+struct CachedResponse {
+    parts: http::response::Parts,
+    body: Bytes,
+    /// Mirrors the expiry recorded in the on-disk entry, so a stale response isn't served
+    /// from memory between the time it expires on disk and the next disk read.
+    expires_at: Option<SystemTime>,
+    /// Size of the on-disk entry this was loaded from or written alongside, so a memory-cache
+    /// hit can still `touch()` the [`LruIndex`] and keep the backing file from looking cold.
+    disk_size: u64,
+}
+
+/// Splits a parsed cache file into its recorded expiry (if any) and the serialized
+/// `http_serde_ext` response value, transparently handling cache entries written before TTL
+/// support was added (where the whole file is the response value, with no wrapper).
+fn parse_cache_entry(parsed: serde_json::Value) -> (Option<u64>, serde_json::Value) {
+    match parsed {
+        serde_json::Value::Object(mut map)
+            if map.contains_key("response") && map.contains_key("expires_at") =>
+        {
+            let expires_at = map.remove("expires_at").and_then(|v| v.as_u64());
+            let response = map.remove("response").unwrap_or(serde_json::Value::Null);
+            (expires_at, response)
+        }
+        other => (None, other),
+    }
+}
+
 fn make_root_cert() -> rcgen::Issuer<'static, rcgen::KeyPair> {
     let mut param = rcgen::CertificateParams::default();
 
@@ -59,7 +164,9 @@ fn save_cache_body(
     path: PathBuf,
     parts: http::response::Parts,
     body: BytesMut,
-) -> Result<(), anyhow::Error> {
+    compression: CacheCompression,
+    expires_at: Option<SystemTime>,
+) -> Result<Option<u64>, anyhow::Error> {
     let path_str = path.to_string_lossy().into_owned();
     tracing::info!(path = path_str, "Finished processing request");
 
@@ -73,7 +180,7 @@ fn save_cache_body(
                 .starts_with("application/pdf")
         {
             tracing::info!("Skipping caching of response with content type {content_type:?}");
-            return Ok(());
+            return Ok(None);
         }
     }
 
@@ -92,45 +199,260 @@ fn save_cache_body(
     let json_response =
         http_serde_ext::response::serialize(&reconstructed, serde_json::value::Serializer)
             .with_context(|| format!("Failed to serialize response for path {path_str}"))?;
-    let json_str = serde_json::to_string(&json_response)
+    let cache_entry = serde_json::json!({
+        "expires_at": expires_at
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+        "response": json_response,
+    });
+    let json_str = serde_json::to_string(&cache_entry)
         .with_context(|| format!("Failed to stringify response for path {path_str}"))?;
 
+    let bytes_to_write = match compression {
+        // Keep the trailing newline for uncompressed entries, so they remain diffable as
+        // plain-text fixtures; compressed formats have no use for it.
+        CacheCompression::None => {
+            let mut bytes = json_str.into_bytes();
+            bytes.push(b'\n');
+            bytes
+        }
+        CacheCompression::Zstd => zstd::encode_all(json_str.as_bytes(), 0)
+            .with_context(|| format!("Failed to zstd-compress response for path {path_str}"))?,
+        CacheCompression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(json_str.as_bytes())
+                .with_context(|| format!("Failed to gzip-compress response for path {path_str}"))?;
+            encoder
+                .finish()
+                .with_context(|| format!("Failed to gzip-compress response for path {path_str}"))?
+        }
+    };
+
     // Write the cache response to a temporary file, and then atomically rename it to the final path.
     // If we have multiple concurrent requests to the same path, one of them will win the race.
     // This is fine for our use case, as it shouldn't matter which successful (by HTTP status code)
     // response is cached.
     let mut tmpfile = tempfile::NamedTempFile::new()
         .with_context(|| format!("Failed to create tempfile for path {path_str}"))?;
+    let written_len = bytes_to_write.len() as u64;
     tmpfile
-        .write_all(json_str.as_bytes())
+        .write_all(&bytes_to_write)
         .with_context(|| format!("Failed to write to file for path {path_str}"))?;
-    tmpfile
-        .write_all(b"\n")
-        .with_context(|| format!("Failed to write EOL newline to file for path {path_str}"))?;
     tmpfile
         .persist(&path)
         .with_context(|| format!("Failed to rename tempfile to {path_str}"))?;
 
     tracing::info!(path = path_str, "Wrote response to cache");
-    Ok(())
+    Ok(Some(written_len))
 }
 
 const HEADER_TRUE: HeaderValue = HeaderValue::from_static("true");
 const HEADER_FALSE: HeaderValue = HeaderValue::from_static("false");
 
+/// Performs the actual upstream fetch for a cache miss, writing the response to `path` if the
+/// configured [`CacheMode`] calls for it. `release` is called exactly once, after it is known
+/// whether (and once) anything will be written to `path` — on the error and non-success paths
+/// that happens immediately, but on the success-and-write path it happens only after the
+/// `StreamingBodyCollector` has finished streaming the body and `save_cache_body` has run, so
+/// that followers waiting on the cache lock don't wake up before the file actually exists.
+async fn fetch_and_cache<
+    E: std::fmt::Debug + 'static,
+    T: Future<Output = Result<hyper::Response<BoxBody<Bytes, E>>, anyhow::Error>>,
+    F: FnOnce() -> T,
+>(
+    state: &ProxyState,
+    path: PathBuf,
+    missing: F,
+    release: impl FnOnce() + Send + 'static,
+) -> Result<(hyper::Response<BoxBody<Bytes, E>>, HeaderValue), anyhow::Error> {
+    tracing::info!("Cache miss: {}", path.to_string_lossy());
+    let response = match missing().await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!(
+                e = e.as_ref() as &dyn std::error::Error,
+                "Failed to forward request"
+            );
+            release();
+            let body = Full::new(Bytes::from(format!("Failed to forward request: {e:?}")));
+            let resp = http::Response::builder()
+                .status(http::StatusCode::BAD_GATEWAY)
+                .body(BoxBody::new(body.map_err(|e| match e {})))
+                .with_context(|| "Failed to build response")?;
+            return Ok((resp, HEADER_FALSE));
+        }
+    };
+    if response.status().is_success() {
+        let (parts, body) = response.into_parts();
+        let mut hyper_response = hyper::Response::from_parts(parts.clone(), body);
+        // We need to clear the extensions in order to be able to serialize the response
+        hyper_response.extensions_mut().clear();
+
+        let freshness = if state.args.ignore_cache_control {
+            Freshness {
+                cacheable: true,
+                expires_at: None,
+            }
+        } else {
+            resp_freshness(&parts.headers, SystemTime::now())
+        };
+
+        let write = freshness.cacheable
+            && match state.args.mode {
+                CacheMode::ReadOnly => false,
+                CacheMode::ReadWrite => true,
+                CacheMode::ReadOldWriteNew => true,
+            };
+
+        // Start streaming the response to the client, running the provided callback once the whole body has been received
+        // This lets us forward streaming responses without needing to wait for the entire response, while
+        // still caching the entire response to disk.
+        // Note that we make a `StreamingBodyCollector` even when `write` is false, so that
+        // the HTTP behavior is consistent regardless of whether `write` is enabled.
+        let compression = state.args.cache_compression;
+        let lru_index = state.lru_index.clone();
+        let memory_cache = state.memory_cache.clone();
+        let in_flight_writes = state.in_flight_writes.clone();
+        let expires_at = freshness.expires_at;
+        let body_collector = hyper_response.map(|b| {
+            StreamingBodyCollector::new(
+                b,
+                Box::new(move |body| {
+                    if write {
+                        let filename = path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned());
+                        let cached_parts = parts.clone();
+                        let cached_body = Bytes::from(body.clone());
+                        // Held until the spawned task below finishes, so graceful shutdown can
+                        // wait for this detached write to land instead of dropping it mid-flight.
+                        let in_flight_guard = in_flight_writes.start();
+                        tokio::task::spawn_blocking(move || {
+                            let _in_flight_guard = in_flight_guard;
+                            match save_cache_body(path, parts, body, compression, expires_at) {
+                                Ok(Some(size)) => {
+                                    if let Some(filename) = filename {
+                                        // Evicting a file from disk must also drop it from the
+                                        // memory tier, or a later memory-cache hit would keep
+                                        // serving a response whose backing file no longer exists
+                                        // (and would re-insert a phantom bookkeeping entry into
+                                        // the LruIndex for it). `touch` can evict the very entry
+                                        // we just wrote (its own size alone still exceeds the
+                                        // cap once every older entry has been evicted) — in that
+                                        // case its backing file is already gone, so skip the
+                                        // insert below instead of re-populating the memory tier
+                                        // for a file that no longer exists on disk.
+                                        let self_evicted = lru_index.as_ref().is_some_and(|lru_index| {
+                                            lru_index.touch(&filename, size, |evicted| {
+                                                memory_cache.invalidate(evicted);
+                                            })
+                                        });
+                                        if !self_evicted {
+                                            // Keep the memory tier's `ReadOldWriteNew` behavior
+                                            // consistent with disk: it's only ever consulted from
+                                            // a branch gated on `use_cache()`, or by a follower
+                                            // that just waited for this very write, so updating
+                                            // it here unconditionally is safe.
+                                            memory_cache.insert(
+                                                filename,
+                                                CachedResponse {
+                                                    parts: cached_parts,
+                                                    body: cached_body,
+                                                    expires_at,
+                                                    disk_size: size,
+                                                },
+                                            );
+                                        }
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(e) => {
+                                    tracing::error!(
+                                        err = e.as_ref() as &dyn std::error::Error,
+                                        "Failed to save cache body"
+                                    );
+                                }
+                            }
+                            release();
+                        });
+                    } else {
+                        release();
+                    }
+                }),
+            )
+        });
+
+        Ok((body_collector.map(|b| BoxBody::new(b)), HEADER_FALSE))
+    } else {
+        tracing::warn!("Skipping caching of non-success response: {:?}", response);
+        release();
+        Ok((response, HEADER_FALSE))
+    }
+}
+
+/// Forwards `request` upstream via `client`, retrying connection failures and 502/503/504
+/// responses up to `retries` times with exponential backoff. Idempotent failures like these are
+/// common on flaky provider connections, and surfacing the first one as a permanent
+/// `BAD_GATEWAY` would otherwise poison the fixture cache for the whole run.
+async fn execute_with_retry(
+    client: &reqwest::Client,
+    retries: u32,
+    request: hyper::Request<Bytes>,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let mut backoff = Duration::from_millis(200);
+    let mut attempt = 0;
+    loop {
+        let mut reqwest_request: reqwest::Request =
+            request.clone().try_into().with_context(|| {
+                "Failed to convert Request from `hyper` to `reqwest`"
+            })?;
+        // Don't explicitly request HTTP2 - let the connection upgrade if the
+        // remote server supports it
+        *reqwest_request.version_mut() = Version::default();
+        let result = client.execute(reqwest_request).await;
+        let retryable = match &result {
+            Ok(response) => matches!(
+                response.status(),
+                http::StatusCode::BAD_GATEWAY
+                    | http::StatusCode::SERVICE_UNAVAILABLE
+                    | http::StatusCode::GATEWAY_TIMEOUT
+            ),
+            Err(_) => true,
+        };
+        if retryable && attempt < retries {
+            tracing::warn!(
+                attempt,
+                ?backoff,
+                "Retrying upstream request after failed attempt: {:?}",
+                result.as_ref().map(reqwest::Response::status)
+            );
+            tokio::time::sleep(backoff).await;
+            // Cap the backoff instead of doubling unbounded: `Duration`'s `Mul<u32>` panics
+            // on overflow, and a large `--upstream-retries` would otherwise turn this into a
+            // hard panic on exactly the kind of flaky upstream this function exists to tolerate.
+            backoff = (backoff * 2).min(Duration::from_secs(30));
+            attempt += 1;
+            continue;
+        }
+        return Ok(result?);
+    }
+}
+
 async fn check_cache<
     E: std::fmt::Debug + 'static,
     T: Future<Output = Result<hyper::Response<BoxBody<Bytes, E>>, anyhow::Error>>,
     F: FnOnce() -> T,
 >(
     start_time: std::time::SystemTime,
-    args: &Args,
+    state: Arc<ProxyState>,
     mut request: hyper::Request<Bytes>,
     missing: F,
 ) -> Result<hyper::Response<BoxBody<Bytes, E>>, anyhow::Error> {
     request.extensions_mut().clear();
     let mut sanitized_header = false;
-    if args.sanitize_bearer_auth {
+    if state.args.sanitize_bearer_auth {
         if let Some(auth_header) = request.headers().get("Authorization") {
             if auth_header.to_str().unwrap().starts_with("Bearer ") {
                 request.headers_mut().insert(
@@ -141,7 +463,7 @@ async fn check_cache<
             }
         }
     }
-    if args.sanitize_aws_sigv4 {
+    if state.args.sanitize_aws_sigv4 {
         let header_names = [
             "authorization",
             "x-amz-date",
@@ -160,7 +482,7 @@ async fn check_cache<
             }
         }
     }
-    if args.sanitize_model_headers {
+    if state.args.sanitize_model_headers {
         let header_names = ["Modal-Key", "Modal-Secret"];
         for header_name in &header_names {
             if request.headers().contains_key(*header_name) {
@@ -181,94 +503,171 @@ async fn check_cache<
         hash
     );
 
-    let path = args.cache_path.join(filename);
+    let path = state.args.cache_path.join(&filename);
     let path_str = path.to_string_lossy().into_owned();
 
-    let use_cache = || match args.mode {
+    let use_cache = || match state.args.mode {
         CacheMode::ReadOnly => Ok::<_, anyhow::Error>(true),
         CacheMode::ReadWrite => Ok(true),
         CacheMode::ReadOldWriteNew => {
-            let file_mtime = std::fs::metadata(&path)
-                .with_context(|| format!("Failed to read cache file metadata for {path_str}"))?
+            let metadata = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                // The LruIndex can evict and delete this file between our `path.exists()`
+                // check and this call running; treat that race as stale rather than a
+                // hard error, matching the file read a few lines below.
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to read cache file metadata for {path_str}"))
+                }
+            };
+            let file_mtime = metadata
                 .modified()
                 .with_context(|| format!("Failed to read cache file mtime for {path_str}"))?;
             Ok(file_mtime <= start_time)
         }
     };
 
-    let (mut resp, cache_hit) = if path.exists() && use_cache()? {
-        tracing::info!(sanitized_header, "Cache hit: {}", path_str);
-        let path_str_clone = path_str.clone();
-        let resp = tokio::task::spawn_blocking(move || {
-            let file = std::fs::read_to_string(path)
-                .with_context(|| format!("Failed to read cache file {path_str}"))?;
-            let response: serde_json::Value = serde_json::from_str(&file).with_context(|| {
-                format!("Failed to deserialize response to JSON from {path_str}")
-            })?;
-            let response: hyper::Response<Bytes> = http_serde_ext::response::deserialize(response)
-                .with_context(|| format!("Failed to deserialize HTTP response from {path_str}"))?;
-            Ok::<_, anyhow::Error>(
-                response.map(|b| BoxBody::new(Full::new(b).map_err(|e| match e {}))),
-            )
-        })
-        .await
-        .with_context(|| format!("Failed to await tokio spawn_blocking for {path_str_clone}"))??;
-        (resp, HEADER_TRUE)
-    } else {
-        tracing::info!("Cache miss: {}", path_str);
-        let response = match missing().await {
-            Ok(response) => response,
-            Err(e) => {
-                tracing::error!(
-                    e = e.as_ref() as &dyn std::error::Error,
-                    "Failed to forward request"
-                );
-                let body = Full::new(Bytes::from(format!("Failed to forward request: {e:?}")));
-                http::Response::builder()
-                    .status(http::StatusCode::BAD_GATEWAY)
-                    .body(BoxBody::new(body.map_err(|e| match e {})))
-                    .with_context(|| "Failed to build response")?
-            }
-        };
-        if response.status().is_success() {
-            let (parts, body) = response.into_parts();
-            let mut hyper_response = hyper::Response::from_parts(parts.clone(), body);
-            // We need to clear the extensions in order to be able to serialize the response
-            hyper_response.extensions_mut().clear();
+    let is_expired = |expires_at: Option<SystemTime>| {
+        !state.args.ignore_cache_control
+            && expires_at.is_some_and(|expires_at| expires_at <= SystemTime::now())
+    };
 
-            let write = match args.mode {
-                CacheMode::ReadOnly => false,
-                CacheMode::ReadWrite => true,
-                CacheMode::ReadOldWriteNew => true,
+    // Set once a follower has woken up after waiting on the leader's `Notify`: the file it's
+    // about to re-check, if present, was necessarily just written during this run (by the
+    // leader it was waiting on), so `use_cache()`'s `ReadOldWriteNew` gate — which exists to
+    // reject exactly that case for a *fresh* request — must not apply to this read. Without
+    // this, every follower would see the leader's freshly-written entry as unusable, fall
+    // through to `cache_locks.acquire`, and perform its own redundant upstream fetch, defeating
+    // single-flight coalescing under the default mode.
+    let mut bypass_mode_gate_after_wake = false;
+
+    let (mut resp, cache_hit) = loop {
+        if path.exists() && (bypass_mode_gate_after_wake || use_cache()?) {
+            let cached = if let Some(cached) = state.memory_cache.get(&filename) {
+                Some(cached)
+            } else {
+                let path_clone = path.clone();
+                let path_str_clone = path_str.clone();
+                let cached = tokio::task::spawn_blocking(move || {
+                    let raw = match std::fs::read(&path_clone) {
+                        Ok(raw) => raw,
+                        // The LruIndex can evict and delete this file between our `path.exists()`
+                        // check above and this read running on its own blocking thread; treat
+                        // that race as a miss instead of a hard error.
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                        Err(e) => {
+                            return Err(e).with_context(|| {
+                                format!("Failed to read cache file {path_str_clone}")
+                            })
+                        }
+                    };
+                    let disk_size = raw.len() as u64;
+                    let file = decompress_cache_body(raw).with_context(|| {
+                        format!("Failed to decompress cache file {path_str_clone}")
+                    })?;
+                    let parsed: serde_json::Value = serde_json::from_slice(&file).with_context(
+                        || format!("Failed to deserialize response to JSON from {path_str_clone}"),
+                    )?;
+                    let (expires_at, response_value) = parse_cache_entry(parsed);
+                    let response: hyper::Response<Bytes> =
+                        http_serde_ext::response::deserialize(response_value).with_context(
+                            || format!("Failed to deserialize HTTP response from {path_str_clone}"),
+                        )?;
+                    let (parts, body) = response.into_parts();
+                    Ok::<_, anyhow::Error>(Some(CachedResponse {
+                        parts,
+                        body,
+                        expires_at: expires_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                        disk_size,
+                    }))
+                })
+                .await
+                .with_context(|| {
+                    format!("Failed to await tokio spawn_blocking for {path_str}")
+                })??;
+                if let Some(cached) = &cached {
+                    if !is_expired(cached.expires_at) {
+                        state.memory_cache.insert(filename.clone(), cached.clone());
+                    }
+                }
+                cached
             };
 
-            // Start streaming the response to the client, running the provided callback once the whole body has been received
-            // This lets us forward streaming responses without needing to wait for the entire response, while
-            // still caching the entire response to disk.
-            // Note that we make a `StreamingBodyCollector` even when `write` is false, so that
-            // the HTTP behavior is consistent regardless of whether `write` is enabled.
-            let body_collector = hyper_response.map(|b| {
-                StreamingBodyCollector::new(
-                    b,
-                    Box::new(move |body| {
-                        if write {
-                            tokio::task::spawn_blocking(move || {
-                                if let Err(e) = save_cache_body(path, parts, body) {
-                                    tracing::error!(
-                                        err = e.as_ref() as &dyn std::error::Error,
-                                        "Failed to save cache body"
-                                    );
-                                }
+            if let Some(cached) = cached {
+                if is_expired(cached.expires_at) {
+                    tracing::info!("Cache entry for {path_str} has expired; treating as a miss");
+                    state.memory_cache.invalidate(&filename);
+                } else {
+                    // Touch on every genuine hit, not just disk reads: a memory-cache hit still
+                    // serves this entry's backing file, and the LruIndex only tracks last-access
+                    // time, not whether the memory tier has been fronting reads for it. Touching
+                    // only here (and not for an expired entry we're about to treat as a miss)
+                    // keeps a stale entry from looking freshly-accessed to the LRU policy just
+                    // because it happened to be looked up. This only updates the LruIndex's
+                    // in-memory bookkeeping, never the file's own mtime — bumping the mtime on a
+                    // read hit would break `CacheMode::ReadOldWriteNew`'s `file_mtime <=
+                    // start_time` check for the very next identical request in this run.
+                    //
+                    // `touch()` can synchronously evict and `std::fs::remove_file` in a loop, so
+                    // run it on a blocking thread rather than the async worker handling this
+                    // request.
+                    if let Some(lru_index) = state.lru_index.clone() {
+                        let filename_clone = filename.clone();
+                        let disk_size = cached.disk_size;
+                        let memory_cache = state.memory_cache.clone();
+                        tokio::task::spawn_blocking(move || {
+                            // Evicting a file from disk must also drop it from the memory tier,
+                            // or a later memory-cache hit would keep serving a response whose
+                            // backing file no longer exists (and would re-insert a phantom
+                            // bookkeeping entry into the LruIndex for it).
+                            lru_index.touch(&filename_clone, disk_size, |evicted| {
+                                memory_cache.invalidate(evicted);
                             });
-                        }
-                    }),
-                )
-            });
+                        })
+                        .await
+                        .with_context(|| {
+                            "Failed to await tokio spawn_blocking for LruIndex::touch"
+                        })?;
+                    }
 
-            (body_collector.map(|b| BoxBody::new(b)), HEADER_FALSE)
-        } else {
-            tracing::warn!("Skipping caching of non-success response: {:?}", response);
-            (response, HEADER_FALSE)
+                    tracing::info!(sanitized_header, "Cache hit: {}", path_str);
+                    let resp = hyper::Response::from_parts(cached.parts, cached.body)
+                        .map(|b| BoxBody::new(Full::new(b).map_err(|e| match e {})));
+                    break (resp, HEADER_TRUE);
+                }
+            } else {
+                tracing::info!(
+                    "Cache file {path_str} disappeared before it could be read (likely evicted); treating as a miss"
+                );
+            }
+        }
+
+        match state.cache_locks.acquire(&filename) {
+            CacheLockGuard::Leader => {
+                let release_state = Arc::clone(&state);
+                let release_key = filename.clone();
+                break fetch_and_cache(&state, path.clone(), missing, move || {
+                    release_state.cache_locks.release(&release_key);
+                })
+                .await?;
+            }
+            CacheLockGuard::Follower(notified) => {
+                let timeout = Duration::from_millis(state.args.cache_lock_timeout_ms);
+                if tokio::time::timeout(timeout, notified).await.is_err() {
+                    tracing::warn!(
+                        "Timed out after {timeout:?} waiting for in-flight request for {path_str}; fetching upstream directly"
+                    );
+                    // Fall through and fetch upstream ourselves without touching the lock: the
+                    // stalled leader still owns it and will release it whenever it eventually
+                    // finishes (or errors out).
+                    break fetch_and_cache(&state, path.clone(), missing, || {}).await?;
+                }
+                // The leader finished (successfully or not); loop back and re-check the cache.
+                // Whatever's on disk now (if anything) is necessarily fresh from this run, so
+                // the mode gate that would otherwise reject a just-written entry doesn't apply.
+                bypass_mode_gate_after_wake = true;
+            }
         }
     };
     // Insert this header at the very end, to ensure that we never store this
@@ -291,6 +690,16 @@ pub enum CacheMode {
     ReadOldWriteNew,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheCompression {
+    /// Write cache entries as plain, uncompressed JSON.
+    None,
+    /// Compress cache entries with zstd.
+    Zstd,
+    /// Compress cache entries with gzip.
+    Gzip,
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
@@ -310,6 +719,67 @@ pub struct Args {
     pub sanitize_model_headers: bool,
     #[arg(long, default_value = "read-old-write-new")]
     pub mode: CacheMode,
+    /// Compression to apply to cache entries written to disk. Existing uncompressed entries
+    /// are still read transparently regardless of this setting.
+    #[arg(long, default_value = "none")]
+    pub cache_compression: CacheCompression,
+    /// How long a request should wait for an identical in-flight request (same cache key) to
+    /// finish populating the cache before giving up and fetching upstream itself.
+    #[arg(long, default_value = "30000")]
+    pub cache_lock_timeout_ms: u64,
+    /// If set, caps the on-disk cache at approximately this many bytes, evicting
+    /// least-recently-used entries as needed. Unbounded if unset.
+    #[arg(long)]
+    pub max_cache_bytes: Option<u64>,
+    /// Number of deserialized responses to keep in an in-memory LRU cache in front of the disk
+    /// cache, to skip the disk read and deserialization on repeated hits within a run.
+    #[arg(long, default_value = "1024")]
+    pub memory_cache_entries: u64,
+    /// If `true`, cache every successful response indefinitely regardless of the upstream
+    /// `Cache-Control`/`Expires` headers, matching the proxy's behavior before TTL support was
+    /// added. Defaults to `true` so existing callers that replay long-lived, committed fixture
+    /// files keep doing so forever instead of suddenly re-fetching (or failing, in
+    /// network-sealed CI) once a fixture's captured `max-age`/`Expires` elapses. Pass `false`
+    /// to opt into strict TTL enforcement.
+    #[arg(long, default_value = "true", action = clap::ArgAction::Set)]
+    pub ignore_cache_control: bool,
+    /// How long to wait for an upstream request to complete, in milliseconds, before treating
+    /// it as a failure. Without this, a hung provider connection can stall a caller indefinitely.
+    #[arg(long, default_value = "30000")]
+    pub upstream_timeout_ms: u64,
+    /// How long to wait for an upstream TCP connection to be established, in milliseconds.
+    #[arg(long, default_value = "10000")]
+    pub upstream_connect_timeout_ms: u64,
+    /// Number of times to retry an upstream request after a connection failure or a
+    /// 502/503/504 response, with exponential backoff, before giving up and returning an error
+    /// to the caller. A response is only cached once a retry attempt actually succeeds, so a
+    /// flaky provider can't poison the fixture cache with an error response.
+    #[arg(long, default_value = "2")]
+    pub upstream_retries: u32,
+}
+
+/// Runtime state shared across all requests handled by the proxy, as opposed to [`Args`] which
+/// is the proxy's static CLI configuration.
+struct ProxyState {
+    args: Args,
+    /// Coordinates concurrent cache misses for the same key so that only one request hits the
+    /// upstream provider; see [`cache_lock`] for details.
+    cache_locks: CacheLocks,
+    /// Tracks cache file sizes and access times so the cache can be kept under
+    /// `args.max_cache_bytes`; `None` if no cap was configured, in which case the cache grows
+    /// unbounded as before.
+    lru_index: Option<Arc<LruIndex>>,
+    /// In-memory hot tier in front of the disk cache, keyed by the same cache hash used for the
+    /// on-disk filename, so that repeated hits within a run skip the disk read and
+    /// `http_serde_ext` deserialization. Sized by `args.memory_cache_entries`.
+    memory_cache: Cache<String, CachedResponse>,
+    /// Tracks detached cache-write tasks so that graceful shutdown can drain them instead of
+    /// dropping them mid-write; see [`inflight`] for details.
+    in_flight_writes: InFlightWrites,
+    /// Tracks requests currently being served, from the moment they're accepted until their
+    /// response is produced, so that graceful shutdown can let them finish instead of the
+    /// accept loop being torn down out from under them; see [`inflight`] for details.
+    in_flight_requests: InFlightWrites,
 }
 
 fn find_duplicate_header(headers: &http::HeaderMap) -> Option<HeaderName> {
@@ -327,7 +797,11 @@ fn is_openrouter_request(uri: &http::Uri) -> bool {
         .unwrap_or(false)
 }
 
-pub async fn run_server(args: Args, server_started: oneshot::Sender<SocketAddr>) {
+pub async fn run_server(
+    args: Args,
+    server_started: oneshot::Sender<SocketAddr>,
+    shutdown: Option<oneshot::Receiver<()>>,
+) {
     use tracing_subscriber::EnvFilter;
 
     #[expect(clippy::print_stderr)]
@@ -342,10 +816,25 @@ pub async fn run_server(args: Args, server_started: oneshot::Sender<SocketAddr>)
 
     let start_time = std::time::SystemTime::now();
 
-    let args = Arc::new(args);
-
     std::fs::create_dir_all(&args.cache_path).expect("Failed to create cache directory");
 
+    let lru_index = args.max_cache_bytes.map(|max_bytes| {
+        Arc::new(
+            LruIndex::scan(args.cache_path.clone(), max_bytes)
+                .expect("Failed to scan cache directory to build LRU index"),
+        )
+    });
+    let memory_cache = Cache::new(args.memory_cache_entries);
+
+    let state = Arc::new(ProxyState {
+        args,
+        cache_locks: CacheLocks::new(),
+        lru_index,
+        memory_cache,
+        in_flight_writes: InFlightWrites::new(),
+        in_flight_requests: InFlightWrites::new(),
+    });
+
     let _ = rustls::crypto::ring::default_provider()
         .install_default()
         .inspect_err(|e| tracing::error!("Failed to install rustls ring provider: {e:?}"));
@@ -358,15 +847,23 @@ pub async fn run_server(args: Args, server_started: oneshot::Sender<SocketAddr>)
         Some(Cache::new(128)),
     );
 
-    let client = reqwest::Client::new();
-    let args_clone = args.clone();
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(state.args.upstream_timeout_ms))
+        .connect_timeout(Duration::from_millis(state.args.upstream_connect_timeout_ms))
+        .build()
+        .expect("Failed to build upstream reqwest client");
+    let state_clone = state.clone();
     let (server_addr, server) = proxy
         .bind(
-            ("127.0.0.1", args.port),
+            ("127.0.0.1", state.args.port),
             service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
                 let client = client.clone();
-                let args = args_clone.clone();
+                let state = state_clone.clone();
+                // Held for the lifetime of this request so that graceful shutdown can wait for
+                // it to finish instead of tearing down the accept loop out from under it.
+                let in_flight_guard = state.in_flight_requests.start();
                 async move {
+                    let _in_flight_guard = in_flight_guard;
                     let (parts, body) = req.into_parts();
 
                     // On OpenRouter requests we want to take advantage of their custom headers identifying the referer.
@@ -413,15 +910,12 @@ pub async fn run_server(args: Args, server_started: oneshot::Sender<SocketAddr>)
                         .with_context(|| "Failed to collect body")?
                         .to_bytes();
                     let bytes_request = hyper::Request::from_parts(parts, body_bytes);
-                    let response = check_cache(start_time, &args, bytes_request.clone(), || async {
-                        let mut request: reqwest::Request =
-                            bytes_request.try_into().with_context(|| {
-                                "Failed to convert Request from `hyper` to `reqwest`"
-                            })?;
-                        // Don't explicitly request HTTP2 - let the connection upgrade if the
-                        // remote server supports it
-                        *request.version_mut() = Version::default();
-                        Ok(http::Response::from(client.execute(request).await?).map(BoxBody::new))
+                    let upstream_retries = state.args.upstream_retries;
+                    let response = check_cache(start_time, state, bytes_request.clone(), || async {
+                        Ok(http::Response::from(
+                            execute_with_retry(&client, upstream_retries, bytes_request).await?,
+                        )
+                        .map(BoxBody::new))
                     })
                     .await?;
 
@@ -432,9 +926,194 @@ pub async fn run_server(args: Args, server_started: oneshot::Sender<SocketAddr>)
         .await
         .unwrap();
 
-    tracing::info!(?args, "HTTP Proxy is listening on http://{server_addr}");
+    tracing::info!(args = ?state.args, "HTTP Proxy is listening on http://{server_addr}");
     server_started
         .send(server_addr)
         .expect("Failed to send server started signal");
-    server.await;
+
+    match shutdown {
+        Some(shutdown) => {
+            // Run the accept loop on its own task rather than racing it directly against
+            // `shutdown`: `MitmProxy` spawns each accepted connection onto its own task
+            // (mirroring http-mitm-proxy), so aborting this task on shutdown only stops new
+            // connections from being accepted and doesn't touch connections already being
+            // served, which we await below instead of cutting off mid-request.
+            let server_handle = tokio::spawn(server);
+            shutdown.await.ok();
+            tracing::info!(
+                "Received shutdown signal; no longer accepting new connections, draining in-flight requests and cache writes"
+            );
+            server_handle.abort();
+            state.in_flight_requests.drain().await;
+        }
+        None => server.await,
+    }
+    state.in_flight_writes.drain().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_cache_body_roundtrips_zstd() {
+        let original = b"{\"hello\":\"world\"}".to_vec();
+        let compressed = zstd::encode_all(original.as_slice(), 0).unwrap();
+        assert_eq!(decompress_cache_body(compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_decompress_cache_body_roundtrips_gzip() {
+        let original = b"{\"hello\":\"world\"}".to_vec();
+        let mut encoder =
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&original).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress_cache_body(compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_decompress_cache_body_passes_through_legacy_uncompressed_entries() {
+        let original = b"{\"hello\":\"world\"}\n".to_vec();
+        assert_eq!(decompress_cache_body(original.clone()).unwrap(), original);
+    }
+
+    #[test]
+    fn test_memory_cache_serves_inserted_entries_without_touching_disk() {
+        let memory_cache: Cache<String, CachedResponse> = Cache::new(10);
+        let entry = CachedResponse {
+            parts: http::Response::new(()).into_parts().0,
+            body: Bytes::from_static(b"cached body"),
+            expires_at: None,
+            disk_size: 42,
+        };
+        memory_cache.insert("key".to_string(), entry.clone());
+
+        let hit = memory_cache
+            .get(&"key".to_string())
+            .expect("expected a memory-cache hit after insert");
+        assert_eq!(hit.body, entry.body);
+        assert_eq!(hit.disk_size, entry.disk_size);
+
+        memory_cache.invalidate(&"key".to_string());
+        assert!(memory_cache.get(&"key".to_string()).is_none());
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> http::HeaderMap {
+        let mut map = http::HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        map
+    }
+
+    #[test]
+    fn test_resp_freshness_no_store_is_not_cacheable() {
+        let freshness = resp_freshness(&headers(&[("cache-control", "no-store")]), SystemTime::now());
+        assert!(!freshness.cacheable);
+    }
+
+    #[test]
+    fn test_resp_freshness_private_is_not_cacheable() {
+        let freshness = resp_freshness(&headers(&[("cache-control", "private")]), SystemTime::now());
+        assert!(!freshness.cacheable);
+    }
+
+    #[test]
+    fn test_resp_freshness_s_maxage_takes_priority_over_max_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let freshness = resp_freshness(
+            &headers(&[("cache-control", "max-age=10, s-maxage=100")]),
+            now,
+        );
+        assert_eq!(freshness.expires_at, Some(now + Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn test_resp_freshness_max_age_used_when_no_s_maxage() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let freshness = resp_freshness(&headers(&[("cache-control", "max-age=30")]), now);
+        assert_eq!(freshness.expires_at, Some(now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_resp_freshness_falls_back_to_expires_header() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let freshness = resp_freshness(
+            &headers(&[("expires", "Sun, 06 Nov 1994 08:49:37 GMT")]),
+            now,
+        );
+        assert!(freshness.expires_at.is_some());
+    }
+
+    #[test]
+    fn test_resp_freshness_no_directives_means_no_ttl() {
+        let freshness = resp_freshness(&http::HeaderMap::new(), SystemTime::now());
+        assert!(freshness.cacheable);
+        assert!(freshness.expires_at.is_none());
+    }
+
+    /// Spawns a task that serves `responses` to successive connections in order, one response
+    /// per connection, then closes the connection (mirroring real upstream servers that don't
+    /// keep a 503/502/504 connection alive for a retry).
+    async fn serve_responses(listener: tokio::net::TcpListener, responses: Vec<&'static str>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        for response in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_retry_retries_a_503_then_succeeds() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_responses(
+            listener,
+            vec![
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+            ],
+        ));
+
+        let client = reqwest::Client::new();
+        let request = hyper::Request::builder()
+            .method("GET")
+            .uri(format!("http://{addr}/"))
+            .body(Bytes::new())
+            .unwrap();
+
+        let response = execute_with_retry(&client, 1, request).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_retry_gives_up_after_exhausting_retries() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_responses(
+            listener,
+            vec![
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            ],
+        ));
+
+        let client = reqwest::Client::new();
+        let request = hyper::Request::builder()
+            .method("GET")
+            .uri(format!("http://{addr}/"))
+            .body(Bytes::new())
+            .unwrap();
+
+        let response = execute_with_retry(&client, 1, request).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    }
 }
@@ -0,0 +1,286 @@
+//! Size-bounded index of on-disk cache entries, modeled on Pingora's `simple_lru::Manager`:
+//! tracks each cache file's size and last-access time in memory, and evicts the
+//! least-recently-used entries when a write or hit would push the tracked total over the cap.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+
+struct Entry {
+    size: u64,
+    last_access: SystemTime,
+}
+
+pub struct LruIndex {
+    cache_path: PathBuf,
+    max_bytes: u64,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl LruIndex {
+    /// Builds the index by scanning `cache_path` for existing cache files, seeding each one's
+    /// last-access time from its file mtime so that ordering survives a restart, then evicts
+    /// down to `max_bytes` if the directory was already over the cap (e.g. `--max-cache-bytes`
+    /// turned on over a `request_cache` directory that grew unbounded before the flag existed).
+    /// There's no other cache tier to invalidate yet at startup, so eviction here is silent.
+    pub fn scan(cache_path: PathBuf, max_bytes: u64) -> Result<Self, anyhow::Error> {
+        let mut entries = HashMap::new();
+        for dir_entry in std::fs::read_dir(&cache_path).with_context(|| {
+            format!(
+                "Failed to read cache directory {}",
+                cache_path.to_string_lossy()
+            )
+        })? {
+            let dir_entry = dir_entry.with_context(|| "Failed to read cache directory entry")?;
+            let metadata = dir_entry
+                .metadata()
+                .with_context(|| "Failed to read cache file metadata")?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let filename = dir_entry.file_name().to_string_lossy().into_owned();
+            let last_access = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.insert(
+                filename,
+                Entry {
+                    size: metadata.len(),
+                    last_access,
+                },
+            );
+        }
+        Self::evict_if_needed(&cache_path, &mut entries, max_bytes, |_| {});
+        Ok(Self {
+            cache_path,
+            max_bytes,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Records an access (cache hit or write) to `filename`, bumping its last-access time to
+    /// now and updating its tracked size, then evicts least-recently-used entries (deleting
+    /// their files) until the tracked total is back under the cap. `on_evict` is called with
+    /// the filename of each evicted entry, so callers can invalidate anything else that's
+    /// keyed off it (e.g. the in-memory hot tier) and not keep serving a response whose backing
+    /// file no longer exists.
+    ///
+    /// This only ever updates the in-memory `last_access`, never the file's own mtime:
+    /// `CacheMode::ReadOldWriteNew` relies on a cache file's mtime staying at its write time to
+    /// decide whether it's still "old" enough to read (see `use_cache()` in lib.rs), and a
+    /// read-hit bumping that mtime would make the very next identical request in the same run
+    /// see the entry as freshly-written and treat it as a miss. A write naturally advances the
+    /// file's mtime on its own (it's rewriting the file), which is all `scan()` needs to reseed
+    /// `last_access` after a restart; a read-only hit's recency does not survive a restart, which
+    /// is an acceptable tradeoff for keeping `ReadOldWriteNew` correct.
+    ///
+    /// Returns `true` if `filename` itself was evicted (e.g. its size alone still exceeds the
+    /// cap after every other entry has been evicted) — when that happens, the just-touched
+    /// file's own disk backing is already gone, so callers must not treat this as a normal
+    /// touch and re-populate anything keyed off it.
+    pub fn touch(&self, filename: &str, size: u64, mut on_evict: impl FnMut(&str)) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let last_access = SystemTime::now();
+        entries.insert(
+            filename.to_string(),
+            Entry { size, last_access },
+        );
+        let mut self_evicted = false;
+        Self::evict_if_needed(&self.cache_path, &mut entries, self.max_bytes, |name| {
+            if name == filename {
+                self_evicted = true;
+            }
+            on_evict(name);
+        });
+        self_evicted
+    }
+
+    fn evict_if_needed(
+        cache_path: &Path,
+        entries: &mut HashMap<String, Entry>,
+        max_bytes: u64,
+        mut on_evict: impl FnMut(&str),
+    ) {
+        let mut total: u64 = entries.values().map(|entry| entry.size).sum();
+        while total > max_bytes {
+            let Some((lru_name, lru_size)) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(name, entry)| (name.clone(), entry.size))
+            else {
+                break;
+            };
+            entries.remove(&lru_name);
+            total = total.saturating_sub(lru_size);
+            on_evict(&lru_name);
+            let path = cache_path.join(&lru_name);
+            match std::fs::remove_file(&path) {
+                Ok(()) => tracing::info!("Evicted LRU cache entry {}", path.to_string_lossy()),
+                Err(e) => tracing::warn!(
+                    "Failed to evict LRU cache entry {}: {e}",
+                    path.to_string_lossy()
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn seed(dir: &Path, entries: &mut HashMap<String, Entry>, data: &[(&str, u64, u64)]) {
+        for (name, size, age_secs) in data {
+            std::fs::write(dir.join(name), b"x").unwrap();
+            entries.insert(
+                (*name).to_string(),
+                Entry {
+                    size: *size,
+                    last_access: SystemTime::UNIX_EPOCH + Duration::from_secs(*age_secs),
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_evict_if_needed_does_nothing_under_the_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = HashMap::new();
+        seed(dir.path(), &mut entries, &[("a", 10, 1)]);
+        LruIndex::evict_if_needed(dir.path(), &mut entries, 100, |_| {});
+        assert!(entries.contains_key("a"));
+        assert!(dir.path().join("a").exists());
+    }
+
+    #[test]
+    fn test_evict_if_needed_evicts_least_recently_used_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = HashMap::new();
+        seed(
+            dir.path(),
+            &mut entries,
+            &[("oldest", 10, 1), ("middle", 10, 2), ("newest", 10, 3)],
+        );
+        LruIndex::evict_if_needed(dir.path(), &mut entries, 20, |_| {});
+        assert!(!entries.contains_key("oldest"));
+        assert!(entries.contains_key("middle"));
+        assert!(entries.contains_key("newest"));
+        assert!(!dir.path().join("oldest").exists());
+    }
+
+    #[test]
+    fn test_evict_if_needed_keeps_evicting_until_back_under_the_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = HashMap::new();
+        seed(
+            dir.path(),
+            &mut entries,
+            &[("a", 10, 1), ("b", 10, 2), ("c", 10, 3), ("d", 10, 4)],
+        );
+        LruIndex::evict_if_needed(dir.path(), &mut entries, 15, |_| {});
+        assert_eq!(entries.len(), 1);
+        assert!(entries.contains_key("d"));
+    }
+
+    #[test]
+    fn test_evict_if_needed_calls_on_evict_for_each_evicted_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut entries = HashMap::new();
+        seed(
+            dir.path(),
+            &mut entries,
+            &[("oldest", 10, 1), ("middle", 10, 2), ("newest", 10, 3)],
+        );
+        let mut evicted = Vec::new();
+        LruIndex::evict_if_needed(dir.path(), &mut entries, 20, |name| {
+            evicted.push(name.to_string())
+        });
+        assert_eq!(evicted, vec!["oldest"]);
+    }
+
+    #[test]
+    fn test_touch_calls_on_evict_for_entries_it_displaces() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("old"), b"x").unwrap();
+        std::fs::write(dir.path().join("new"), b"x").unwrap();
+        let index = LruIndex {
+            cache_path: dir.path().to_path_buf(),
+            max_bytes: 10,
+            entries: Mutex::new(HashMap::from([(
+                "old".to_string(),
+                Entry {
+                    size: 10,
+                    last_access: SystemTime::UNIX_EPOCH,
+                },
+            )])),
+        };
+        let mut evicted = Vec::new();
+        let self_evicted = index.touch("new", 10, |name| evicted.push(name.to_string()));
+        assert_eq!(evicted, vec!["old"]);
+        assert!(!self_evicted);
+    }
+
+    #[test]
+    fn test_touch_reports_self_eviction_when_the_touched_entry_cannot_fit() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("new"), b"x").unwrap();
+        let index = LruIndex {
+            cache_path: dir.path().to_path_buf(),
+            max_bytes: 10,
+            entries: Mutex::new(HashMap::new()),
+        };
+        let self_evicted = index.touch("new", 20, |_| {});
+        assert!(self_evicted);
+    }
+
+    #[test]
+    fn test_touch_does_not_mutate_the_file_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("entry"), b"x").unwrap();
+        std::fs::File::open(dir.path().join("entry"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        let index = LruIndex {
+            cache_path: dir.path().to_path_buf(),
+            max_bytes: 100,
+            entries: Mutex::new(HashMap::from([(
+                "entry".to_string(),
+                Entry {
+                    size: 1,
+                    last_access: SystemTime::UNIX_EPOCH,
+                },
+            )])),
+        };
+        index.touch("entry", 1, |_| {});
+        let mtime = std::fs::metadata(dir.path().join("entry"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(mtime, SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_scan_evicts_down_to_the_cap_for_an_already_over_cap_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("old"), vec![0u8; 10]).unwrap();
+        std::fs::File::open(dir.path().join("old"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        std::fs::write(dir.path().join("new"), vec![0u8; 10]).unwrap();
+        std::fs::File::open(dir.path().join("new"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(2))
+            .unwrap();
+
+        let index = LruIndex::scan(dir.path().to_path_buf(), 10).unwrap();
+
+        assert!(!dir.path().join("old").exists());
+        assert!(dir.path().join("new").exists());
+        assert!(!index.entries.lock().unwrap().contains_key("old"));
+    }
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 
 /// Deserializes a "doubly-serialized" field of a struct.
@@ -119,6 +119,139 @@ where
     }
 }
 
+/// Serializes a value as a "doubly-serialized" field of a struct, i.e. the inverse of
+/// `deserialize_json_string`. The value is serialized to a JSON string, and that string is then
+/// written as the field's value, so that the resulting field is itself a JSON string containing
+/// the serialized value.
+pub fn serialize_json_string<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    let json_str = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&json_str)
+}
+
+/// Serializes a value as a "doubly-serialized" field of a struct, i.e. the inverse of
+/// `deserialize_defaulted_json_string`. This behaves identically to `serialize_json_string`;
+/// it exists so that a field can use a single `deserialize_defaulted_json_string` /
+/// `serialize_defaulted_json_string` pair for symmetry with the read side.
+pub fn serialize_defaulted_json_string<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    serialize_json_string(value, serializer)
+}
+
+/// Serializes an `Option<T>` as a "doubly-serialized" field of a struct, i.e. the inverse of
+/// `deserialize_optional_json_string`. `None` is written as the empty string `""`, matching the
+/// ClickHouse empty-string-as-null convention already honored by the deserializer.
+pub fn serialize_optional_json_string<S, T>(
+    value: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    match value {
+        Some(value) => serialize_json_string(value, serializer),
+        None => serializer.serialize_str(""),
+    }
+}
+
+/// `serialize`/`deserialize` pair for a required "doubly-serialized" field, usable via a single
+/// `#[serde(with = "json_string")]` attribute.
+pub mod json_string {
+    pub use super::{deserialize_json_string as deserialize, serialize_json_string as serialize};
+}
+
+/// `serialize`/`deserialize` pair for an optional "doubly-serialized" field, usable via a single
+/// `#[serde(with = "json_string_opt")]` attribute. `None` round-trips through the empty string.
+pub mod json_string_opt {
+    pub use super::{
+        deserialize_optional_json_string as deserialize,
+        serialize_optional_json_string as serialize,
+    };
+}
+
+/// `serialize`/`deserialize` pair for a defaulted "doubly-serialized" field, usable via a single
+/// `#[serde(with = "json_string_defaulted")]` attribute. The empty string deserializes to
+/// `T::default()`; serializing always writes the JSON-encoded value, so `T::default()` is
+/// written as its JSON form, not as the empty string.
+pub mod json_string_defaulted {
+    pub use super::{
+        deserialize_defaulted_json_string as deserialize,
+        serialize_defaulted_json_string as serialize,
+    };
+}
+
+/// Deserializes a "doubly-serialized" field of a struct without parsing the inner payload.
+/// This is a zero-copy variant of `deserialize_json_string`: the outer string layer is
+/// unwrapped and validated as well-formed JSON, but the inner value is returned as a
+/// `Box<RawValue>` rather than being fully parsed into `T`. This avoids an allocation and parse
+/// pass for fields (like ClickHouse `input`/`output` columns) that are typically forwarded
+/// verbatim without being inspected.
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Outer {
+///     #[serde(deserialize_with = "deserialize_json_string_raw")]
+///     inner: Box<serde_json::value::RawValue>,
+/// }
+///
+/// let outer = serde_json::from_str::<Outer>("{\"inner\": \"{\\"foo\\": 1}\"}")?;
+/// assert_eq!(outer.inner.get(), r#"{"foo": 1}"#);
+/// ```
+pub fn deserialize_json_string_raw<'de, D>(
+    deserializer: D,
+) -> Result<Box<serde_json::value::RawValue>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let json_str = String::deserialize(deserializer)?;
+    serde_json::value::RawValue::from_string(json_str).map_err(serde::de::Error::custom)
+}
+
+/// Like `deserialize_json_string_raw`, but allows the string `""` to stand in for
+/// `RawValue` containing `null`, matching `deserialize_defaulted_json_string`.
+pub fn deserialize_defaulted_json_string_raw<'de, D>(
+    deserializer: D,
+) -> Result<Box<serde_json::value::RawValue>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let json_str = String::deserialize(deserializer)?;
+    if json_str.is_empty() {
+        return serde_json::value::RawValue::from_string("null".to_string())
+            .map_err(serde::de::Error::custom);
+    }
+    serde_json::value::RawValue::from_string(json_str).map_err(serde::de::Error::custom)
+}
+
+/// Like `deserialize_json_string_raw`, but treats `null` or the empty string `""` (the
+/// ClickHouse empty-string-as-null convention) as `None`, matching
+/// `deserialize_optional_json_string`.
+pub fn deserialize_optional_json_string_raw<'de, D>(
+    deserializer: D,
+) -> Result<Option<Box<serde_json::value::RawValue>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let opt_json_str: Option<String> = Option::deserialize(deserializer)?;
+    match opt_json_str {
+        Some(json_str) => {
+            if json_str.is_empty() {
+                return Ok(None);
+            }
+            serde_json::value::RawValue::from_string(json_str)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+        None => Ok(None),
+    }
+}
+
 /// Deserializes a "maybe-doubly-serialized" field of a struct.
 /// If you have a struct like this:
 /// ```ignore
@@ -160,6 +293,42 @@ where
     }
 }
 
+/// Like `deserialize_string_or_parsed_json`, but when the field is a string that fails strict
+/// `serde_json` parsing, falls back to a JSON5-tolerant parse before giving up. This is meant
+/// for embedded config-like payloads (tool-call arguments, templated config blobs) that come
+/// from models or hand-authored fixtures and may contain trailing commas, `//`/`/* */` comments,
+/// single-quoted or unquoted object keys, hex integer literals like `0x2A`, or the non-finite
+/// float tokens `Infinity`/`NaN` — none of which strict JSON accepts. The strict parse is always
+/// attempted first so the common case stays fast.
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Outer {
+///     #[serde(deserialize_with = "deserialize_lenient_string_or_parsed_json")]
+///     inner: Inner,
+/// }
+///
+/// // Trailing comma and an unquoted key, neither valid strict JSON:
+/// let outer = serde_json::from_str::<Outer>("{\"inner\": \"{foo: 1, \\\"bar\\\": \\\"baz\\\",}\"}")?;
+/// assert_eq!(outer.inner.foo, 1);
+/// ```
+pub fn deserialize_lenient_string_or_parsed_json<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value: Value = Deserialize::deserialize(deserializer)?;
+    match value {
+        Value::String(s) => serde_json::from_str(&s).or_else(|strict_err| {
+            json5::from_str(&s).map_err(|lenient_err| {
+                serde::de::Error::custom(format!(
+                    "failed to parse as strict JSON ({strict_err}) or as JSON5 ({lenient_err})"
+                ))
+            })
+        }),
+        _ => serde_json::from_value(value).map_err(serde::de::Error::custom),
+    }
+}
+
 /// Deserializes an optional "maybe-doubly-serialized" field of a struct.
 /// If you have a struct like this:
 /// ```ignore
@@ -221,6 +390,82 @@ where
     }
 }
 
+/// A JSON value whose object keys preserve their original insertion order, independent of
+/// serde_json's crate-wide `preserve_order` feature. That feature backs *every*
+/// `serde_json::Map` in the binary with an `IndexMap` instead of a `BTreeMap`, which would
+/// change the iteration/serialization order of every other `serde_json::Value` already in the
+/// codebase, not just the call sites that want it; this type instead goes through
+/// `indexmap::IndexMap` directly, so ordering is opt-in per field, at the places that use this
+/// type and the helpers below.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OrderedJsonValue {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<OrderedJsonValue>),
+    Object(indexmap::IndexMap<String, OrderedJsonValue>),
+}
+
+impl OrderedJsonValue {
+    /// Returns the underlying map if this is an `Object`, mirroring `serde_json::Value::as_object`.
+    pub fn as_object(&self) -> Option<&indexmap::IndexMap<String, OrderedJsonValue>> {
+        match self {
+            OrderedJsonValue::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an embedded JSON string into an [`OrderedJsonValue`], preserving the original
+/// insertion order of any object keys.
+pub fn parse_json_value_ordered(s: &str) -> Result<OrderedJsonValue, serde_json::Error> {
+    serde_json::from_str(s)
+}
+
+/// Like `deserialize_string_or_parsed_json`, but for untyped payloads (`T = OrderedJsonValue`)
+/// where object key order needs to survive a round trip through the embedded JSON string. This
+/// makes golden-file diffs of stored inputs/outputs stable, and ensures re-serializing a
+/// doubly-serialized object yields the same key order it had when written.
+pub fn deserialize_string_or_parsed_json_ordered<'de, D>(
+    deserializer: D,
+) -> Result<OrderedJsonValue, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: OrderedJsonValue = Deserialize::deserialize(deserializer)?;
+    match value {
+        OrderedJsonValue::String(s) => {
+            parse_json_value_ordered(&s).map_err(serde::de::Error::custom)
+        }
+        value => Ok(value),
+    }
+}
+
+/// `Option` variant of `deserialize_string_or_parsed_json_ordered`; `null` or the empty string
+/// `""` (the ClickHouse empty-string-as-null convention) deserializes to `None`.
+pub fn deserialize_optional_string_or_parsed_json_ordered<'de, D>(
+    deserializer: D,
+) -> Result<Option<OrderedJsonValue>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: OrderedJsonValue = Deserialize::deserialize(deserializer)?;
+    match value {
+        OrderedJsonValue::Null => Ok(None),
+        OrderedJsonValue::String(s) => {
+            if s.is_empty() {
+                return Ok(None);
+            }
+            parse_json_value_ordered(&s)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+        value => Ok(Some(value)),
+    }
+}
+
 /// Deserializes a defaulted "maybe-doubly-serialized" field of a struct.
 /// If you have a struct like this:
 /// ```ignore
@@ -323,63 +568,141 @@ where
     }
 }
 
-/// Like `deserialize_option_u64`, but requires a number to be present.
-pub fn deserialize_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+/// A `serde::de::Visitor` that accepts a JSON integer literal that fits in `i64`/`u64` (the only
+/// two primitive visit methods serde_json's `deserialize_any` ever actually calls: any value
+/// that overflows both arrives as a float via `visit_f64`, which this doesn't override) or a
+/// decimal string, and converts it into `T` via `TryFrom`/`FromStr`.
+///
+/// This is a hand-written visitor rather than an untagged `enum Helper { String, Number(T) }`,
+/// so that a number is converted into `T` directly from the `visit_i64`/`visit_u64` call the
+/// format makes, without first buffering the input into a content tree and replaying it.
+struct StringyIntVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T> serde::de::Visitor<'de> for StringyIntVisitor<T>
 where
-    D: Deserializer<'de>,
+    T: TryFrom<i64> + TryFrom<u64> + TryFrom<i128> + TryFrom<u128> + std::str::FromStr,
+    <T as TryFrom<i64>>::Error: std::fmt::Display,
+    <T as TryFrom<u64>>::Error: std::fmt::Display,
+    <T as TryFrom<i128>>::Error: std::fmt::Display,
+    <T as TryFrom<u128>>::Error: std::fmt::Display,
+    <T as std::str::FromStr>::Err: std::fmt::Display,
 {
-    use serde::de::Error;
+    type Value = T;
 
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum Helper {
-        String(String),
-        Number(u64),
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("a JSON integer or a decimal string")
     }
 
-    match Helper::deserialize(deserializer)? {
-        Helper::String(s) => {
-            if s.is_empty() {
-                Err(D::Error::custom("empty string is not a valid u64"))
-            } else {
-                s.parse::<u64>()
-                    .map_err(|_| D::Error::custom(format!("invalid u64 string: '{s}'")))
-            }
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<T, E> {
+        T::try_from(v).map_err(|e| E::custom(format!("integer out of range: {e}")))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<T, E> {
+        T::try_from(v).map_err(|e| E::custom(format!("integer out of range: {e}")))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<T, E> {
+        if v.is_empty() {
+            Err(E::custom("empty string is not a valid integer"))
+        } else {
+            v.parse::<T>()
+                .map_err(|e| E::custom(format!("invalid integer string '{v}': {e}")))
         }
-        Helper::Number(n) => Ok(n),
     }
 }
 
-/// In JSON, large numbers cannot be represented as numbers so we instead represent them as strings.
-/// This function deserializes them as strings and then parses them as u64s.
-/// It also handles the case where the value is null or a number as usual.
-pub fn deserialize_option_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+/// Generic version of `deserialize_u64` and friends, parameterized over the target integer
+/// type. Large numbers (and negative numbers, and 128-bit identifiers) arrive from
+/// ClickHouse/JSON as strings because JSON numbers can't losslessly represent them; this accepts
+/// either a JSON number or a decimal string and errors on an empty string. Supports `i64`,
+/// `u64`, `i128`, and `u128`, but a bare (non-quoted) number literal only round-trips for values
+/// that fit in `i64`/`u64`: serde_json's default (non-`arbitrary_precision`) parser converts any
+/// JSON integer literal too wide for those into an `f64` before this ever sees it. A genuine
+/// 128-bit value must be a quoted decimal string.
+///
+/// Call sites specify the target type explicitly (the deserializer type is inferred), e.g.
+/// `#[serde(deserialize_with = "deserialize_stringy_int::<i128, _>")]`.
+pub fn deserialize_stringy_int<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
+    T: TryFrom<i64> + TryFrom<u64> + TryFrom<i128> + TryFrom<u128> + std::str::FromStr,
+    <T as TryFrom<i64>>::Error: std::fmt::Display,
+    <T as TryFrom<u64>>::Error: std::fmt::Display,
+    <T as TryFrom<i128>>::Error: std::fmt::Display,
+    <T as TryFrom<u128>>::Error: std::fmt::Display,
+    <T as std::str::FromStr>::Err: std::fmt::Display,
 {
-    use serde::de::Error;
+    deserializer.deserialize_any(StringyIntVisitor(std::marker::PhantomData))
+}
 
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum Helper {
-        String(String),
-        Number(u64),
-        Null,
-    }
+/// `Option` variant of `deserialize_stringy_int`; `null` deserializes to `None`.
+pub fn deserialize_option_stringy_int<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<i64> + TryFrom<u64> + TryFrom<i128> + TryFrom<u128> + std::str::FromStr,
+    <T as TryFrom<i64>>::Error: std::fmt::Display,
+    <T as TryFrom<u64>>::Error: std::fmt::Display,
+    <T as TryFrom<i128>>::Error: std::fmt::Display,
+    <T as TryFrom<u128>>::Error: std::fmt::Display,
+    <T as std::str::FromStr>::Err: std::fmt::Display,
+{
+    struct OptionStringyIntVisitor<T>(std::marker::PhantomData<T>);
 
-    match Helper::deserialize(deserializer)? {
-        Helper::String(s) => {
-            if s.is_empty() {
-                Err(D::Error::custom("empty string is not a valid u64"))
-            } else {
-                s.parse::<u64>()
-                    .map(Some)
-                    .map_err(|_| D::Error::custom(format!("invalid u64 string: '{s}'")))
-            }
+    impl<'de, T> serde::de::Visitor<'de> for OptionStringyIntVisitor<T>
+    where
+        T: TryFrom<i64> + TryFrom<u64> + TryFrom<i128> + TryFrom<u128> + std::str::FromStr,
+        <T as TryFrom<i64>>::Error: std::fmt::Display,
+        <T as TryFrom<u64>>::Error: std::fmt::Display,
+        <T as TryFrom<i128>>::Error: std::fmt::Display,
+        <T as TryFrom<u128>>::Error: std::fmt::Display,
+        <T as std::str::FromStr>::Err: std::fmt::Display,
+    {
+        type Value = Option<T>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("a JSON integer, a decimal string, or null")
+        }
+
+        fn visit_none<E: serde::de::Error>(self) -> Result<Option<T>, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Option<T>, E> {
+            Ok(None)
+        }
+
+        // `visit_some` is the only entry point `deserialize_option` actually calls for a
+        // present value, for every format (including serde_json): a format either signals
+        // absence directly to `visit_none`/`visit_unit`, or hands the present value to
+        // `visit_some` via its own deserializer, which is where `StringyIntVisitor` ends up
+        // running. There's no format-level hook that calls a primitive `visit_*` method
+        // directly on the `Option` visitor, so this intentionally doesn't duplicate them here.
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+            deserializer
+                .deserialize_any(StringyIntVisitor(std::marker::PhantomData))
+                .map(Some)
         }
-        Helper::Number(n) => Ok(Some(n)),
-        Helper::Null => Ok(None),
     }
+
+    deserializer.deserialize_option(OptionStringyIntVisitor(std::marker::PhantomData))
+}
+
+/// Like `deserialize_option_u64`, but requires a number to be present.
+pub fn deserialize_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_stringy_int::<u64, D>(deserializer)
+}
+
+/// In JSON, large numbers cannot be represented as numbers so we instead represent them as strings.
+/// This function deserializes them as strings and then parses them as u64s.
+/// It also handles the case where the value is null or a number as usual.
+pub fn deserialize_option_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_option_stringy_int::<u64, D>(deserializer)
 }
 
 #[cfg(test)]
@@ -518,6 +841,67 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[derive(Debug, Deserialize)]
+    struct TestLenientStringOrParsedOuter {
+        #[serde(deserialize_with = "deserialize_lenient_string_or_parsed_json")]
+        inner: TestStruct,
+    }
+
+    #[test]
+    fn test_deserialize_lenient_string_or_parsed_json_strict_still_works() {
+        let json = r#"{"inner": "{\"foo\": 42, \"bar\": \"test\"}"}"#;
+        let result: TestLenientStringOrParsedOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.foo, 42);
+        assert_eq!(result.inner.bar, "test");
+    }
+
+    #[test]
+    fn test_deserialize_lenient_string_or_parsed_json_trailing_comma_and_unquoted_key() {
+        let json = r#"{"inner": "{foo: 42, \"bar\": \"test\",}"}"#;
+        let result: TestLenientStringOrParsedOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.foo, 42);
+        assert_eq!(result.inner.bar, "test");
+    }
+
+    #[test]
+    fn test_deserialize_lenient_string_or_parsed_json_still_rejects_garbage() {
+        let json = r#"{"inner": "not json at all"}"#;
+        let result: Result<TestLenientStringOrParsedOuter, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_lenient_string_or_parsed_json_hex_integer_literal() {
+        let json = r#"{"inner": "{\"foo\": 0x2A, \"bar\": \"test\"}"}"#;
+        let result: TestLenientStringOrParsedOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.foo, 42);
+        assert_eq!(result.inner.bar, "test");
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestLenientFloatStruct {
+        foo: f64,
+        bar: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestLenientFloatOuter {
+        #[serde(deserialize_with = "deserialize_lenient_string_or_parsed_json")]
+        inner: TestLenientFloatStruct,
+    }
+
+    #[test]
+    fn test_deserialize_lenient_string_or_parsed_json_non_finite_float_tokens() {
+        let json = r#"{"inner": "{\"foo\": Infinity, \"bar\": \"test\"}"}"#;
+        let result: TestLenientFloatOuter = serde_json::from_str(json).unwrap();
+        assert!(result.inner.foo.is_infinite());
+        assert_eq!(result.inner.bar, "test");
+
+        let json = r#"{"inner": "{\"foo\": NaN, \"bar\": \"test\"}"}"#;
+        let result: TestLenientFloatOuter = serde_json::from_str(json).unwrap();
+        assert!(result.inner.foo.is_nan());
+    }
+
     #[test]
     fn test_deserialize_optional_string_or_parsed_json_from_string() {
         let json = r#"{"inner": "{\"foo\": 42, \"bar\": \"test\"}"}"#;
@@ -559,6 +943,47 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[derive(Debug, Deserialize)]
+    struct TestOrderedOuter {
+        #[serde(deserialize_with = "deserialize_string_or_parsed_json_ordered")]
+        inner: OrderedJsonValue,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestOptionalOrderedOuter {
+        #[serde(deserialize_with = "deserialize_optional_string_or_parsed_json_ordered")]
+        inner: Option<OrderedJsonValue>,
+    }
+
+    #[test]
+    fn test_deserialize_string_or_parsed_json_ordered_preserves_key_order() {
+        let json = r#"{"inner": "{\"zeta\": 1, \"alpha\": 2, \"mu\": 3}"}"#;
+        let result: TestOrderedOuter = serde_json::from_str(json).unwrap();
+        let keys: Vec<&String> = result.inner.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["zeta", "alpha", "mu"]);
+        // Re-serializing should reproduce the exact same key order it was written with.
+        assert_eq!(
+            serde_json::to_string(&result.inner).unwrap(),
+            r#"{"zeta":1,"alpha":2,"mu":3}"#
+        );
+    }
+
+    #[test]
+    fn test_deserialize_optional_string_or_parsed_json_ordered_empty_string() {
+        let json = r#"{"inner": ""}"#;
+        let result: TestOptionalOrderedOuter = serde_json::from_str(json).unwrap();
+        assert!(result.inner.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_optional_string_or_parsed_json_ordered_some() {
+        let json = r#"{"inner": "{\"zeta\": 1, \"alpha\": 2}"}"#;
+        let result: TestOptionalOrderedOuter = serde_json::from_str(json).unwrap();
+        let inner = result.inner.unwrap();
+        let keys: Vec<&String> = inner.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["zeta", "alpha"]);
+    }
+
     #[test]
     fn test_deserialize_defaulted_string_or_parsed_json_from_string() {
         let json = r#"{"inner": "{\"foo\": 42, \"bar\": \"test\"}"}"#;
@@ -640,6 +1065,117 @@ mod tests {
         assert_eq!(result.inner, 1234567890);
     }
 
+    #[derive(Debug, Deserialize)]
+    struct TestI64Outer {
+        #[serde(deserialize_with = "deserialize_stringy_int::<i64, _>")]
+        inner: i64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestOptionI64Outer {
+        #[serde(deserialize_with = "deserialize_option_stringy_int::<i64, _>")]
+        inner: Option<i64>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestI128Outer {
+        #[serde(deserialize_with = "deserialize_stringy_int::<i128, _>")]
+        inner: i128,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestU128Outer {
+        #[serde(deserialize_with = "deserialize_stringy_int::<u128, _>")]
+        inner: u128,
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_negative_string() {
+        let json = r#"{"inner": "-42"}"#;
+        let result: TestI64Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, -42);
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_negative_number() {
+        let json = r#"{"inner": -42}"#;
+        let result: TestI64Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, -42);
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_empty_string() {
+        let json = r#"{"inner": ""}"#;
+        let result: Result<TestI64Outer, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_option_stringy_int_null() {
+        let json = r#"{"inner": null}"#;
+        let result: TestOptionI64Outer = serde_json::from_str(json).unwrap();
+        assert!(result.inner.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_option_stringy_int_present_number() {
+        let json = r#"{"inner": -42}"#;
+        let result: TestOptionI64Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, Some(-42));
+    }
+
+    #[test]
+    fn test_deserialize_option_stringy_int_present_string() {
+        let json = r#"{"inner": "-42"}"#;
+        let result: TestOptionI64Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, Some(-42));
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_i128_string() {
+        let json = r#"{"inner": "170141183460469231731687303715884105727"}"#;
+        let result: TestI128Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, i128::MAX);
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_u128_string() {
+        let json = r#"{"inner": "340282366920938463463374607431768211455"}"#;
+        let result: TestU128Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, u128::MAX);
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_i128_number() {
+        let json = r#"{"inner": 42}"#;
+        let result: TestI128Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, 42);
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_u128_number() {
+        let json = r#"{"inner": 42}"#;
+        let result: TestU128Outer = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner, 42);
+    }
+
+    /// A genuinely 128-bit bare (non-quoted) number literal doesn't round-trip: serde_json's
+    /// default parser converts anything that overflows `i64`/`u64` into an `f64` before
+    /// `StringyIntVisitor` ever sees it, so this must be passed as a quoted string instead.
+    #[test]
+    fn test_deserialize_stringy_int_i128_number_too_wide_for_i64_errors() {
+        let json = r#"{"inner": 170141183460469231731687303715884105727}"#;
+        let result: Result<TestI128Outer, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_stringy_int_u128_number_too_wide_for_u64_errors() {
+        let json = r#"{"inner": 340282366920938463463374607431768211455}"#;
+        let result: Result<TestU128Outer, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_deserialize_defaulted_json_string_empty_string() {
         let json = r#"{"inner": ""}"#;
@@ -662,6 +1198,115 @@ mod tests {
         assert_eq!(result.inner.bar, "test");
     }
 
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestJsonStringWithOuter {
+        #[serde(with = "json_string")]
+        inner: TestStruct,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestJsonStringOptWithOuter {
+        #[serde(with = "json_string_opt")]
+        inner: Option<TestStruct>,
+    }
+
+    #[test]
+    fn test_serialize_json_string_round_trip() {
+        let outer = TestJsonStringWithOuter {
+            inner: TestStruct {
+                foo: 42,
+                bar: "test".to_string(),
+            },
+        };
+        let json = serde_json::to_string(&outer).unwrap();
+        assert_eq!(json, r#"{"inner":"{\"foo\":42,\"bar\":\"test\"}"}"#);
+        let round_tripped: TestJsonStringWithOuter = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped, outer);
+    }
+
+    #[test]
+    fn test_serialize_optional_json_string_some() {
+        let outer = TestJsonStringOptWithOuter {
+            inner: Some(TestStruct {
+                foo: 42,
+                bar: "test".to_string(),
+            }),
+        };
+        let json = serde_json::to_string(&outer).unwrap();
+        assert_eq!(json, r#"{"inner":"{\"foo\":42,\"bar\":\"test\"}"}"#);
+        let round_tripped: TestJsonStringOptWithOuter = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped, outer);
+    }
+
+    #[test]
+    fn test_serialize_optional_json_string_none() {
+        let outer = TestJsonStringOptWithOuter { inner: None };
+        let json = serde_json::to_string(&outer).unwrap();
+        assert_eq!(json, r#"{"inner":""}"#);
+        let round_tripped: TestJsonStringOptWithOuter = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_tripped, outer);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestRawOuter {
+        #[serde(deserialize_with = "deserialize_json_string_raw")]
+        inner: Box<serde_json::value::RawValue>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestOptionalRawOuter {
+        #[serde(deserialize_with = "deserialize_optional_json_string_raw")]
+        inner: Option<Box<serde_json::value::RawValue>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct TestDefaultedRawOuter {
+        #[serde(deserialize_with = "deserialize_defaulted_json_string_raw")]
+        inner: Box<serde_json::value::RawValue>,
+    }
+
+    #[test]
+    fn test_deserialize_json_string_raw_preserves_inner_bytes() {
+        let json = r#"{"inner": "{\"foo\": 42, \"bar\":   \"test\"}"}"#;
+        let result: TestRawOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.get(), r#"{"foo": 42, "bar":   "test"}"#);
+    }
+
+    #[test]
+    fn test_deserialize_json_string_raw_invalid_json() {
+        let json = r#"{"inner": "{\"foo\": 42, \"bar\": invalid"}"#;
+        let result: Result<TestRawOuter, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_optional_json_string_raw_some() {
+        let json = r#"{"inner": "{\"foo\": 42}"}"#;
+        let result: TestOptionalRawOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.unwrap().get(), r#"{"foo": 42}"#);
+    }
+
+    #[test]
+    fn test_deserialize_optional_json_string_raw_empty_string() {
+        let json = r#"{"inner": ""}"#;
+        let result: TestOptionalRawOuter = serde_json::from_str(json).unwrap();
+        assert!(result.inner.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_optional_json_string_raw_null() {
+        let json = r#"{"inner": null}"#;
+        let result: TestOptionalRawOuter = serde_json::from_str(json).unwrap();
+        assert!(result.inner.is_none());
+    }
+
+    #[test]
+    fn test_deserialize_defaulted_json_string_raw_empty_string() {
+        let json = r#"{"inner": ""}"#;
+        let result: TestDefaultedRawOuter = serde_json::from_str(json).unwrap();
+        assert_eq!(result.inner.get(), "null");
+    }
+
     #[test]
     fn test_deserialize_defaulted_string_null() {
         let json = r#"{"inner": null}"#;